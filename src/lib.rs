@@ -1,9 +1,34 @@
-use pyo3::{exceptions::PyValueError, prelude::*, types::PyString};
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyBytes, PyString, PyType},
+};
 
 use ::daachorse::{
+    bytewise::{DoubleArrayAhoCorasick, DoubleArrayAhoCorasickBuilder},
     CharwiseDoubleArrayAhoCorasick, CharwiseDoubleArrayAhoCorasickBuilder, MatchKind,
 };
 
+/// Format version of the binary blob produced by [`Automaton::serialize`].
+///
+/// Bumped whenever the layout below changes so that [`Automaton::deserialize`] can reject
+/// blobs it no longer knows how to read instead of misinterpreting their bytes.
+const SERIALIZE_FORMAT_VERSION: u8 = 3;
+
+/// ASCII-lowercases `haystack`, leaving every non-ASCII byte untouched.
+///
+/// This only folds the 26 ASCII letters, so it never attempts (and never needs) full Unicode
+/// case folding. Since ASCII letters and their lowercase counterparts are both single UTF-8
+/// bytes, the result has exactly the same length and char boundaries as `haystack`, which keeps
+/// char-offset tuples returned by `find`-family methods valid for the original haystack.
+fn ascii_fold(haystack: &str) -> String {
+    let bytes: Vec<u8> = haystack.bytes().map(|b| b.to_ascii_lowercase()).collect();
+    // Safety: lowercasing ASCII bytes can't turn a valid UTF-8 byte sequence into an invalid
+    // one, since multi-byte continuation/lead bytes all have their high bit set and therefore
+    // fall outside the ASCII uppercase range `to_ascii_lowercase` touches.
+    unsafe { String::from_utf8_unchecked(bytes) }
+}
+
 /// An Aho-Corasick automaton using the compact double-array data structure.
 ///
 /// Examples:
@@ -13,37 +38,96 @@ use ::daachorse::{
 ///     >>> pma.find('abcd')
 ///     [(0, 1, 2), (1, 4, 0)]
 ///
+/// By default each pattern's value is its position in `patterns`. Pass `values` to associate
+/// patterns with your own integer payloads (category IDs, token IDs, etc.) instead; `find` and
+/// `find_overlapping` then report those values, while `find_as_strings` still resolves back to
+/// the original pattern text.
+///
+/// Pass `case_insensitive=True` to match ASCII letters regardless of case (`'A'..='Z'` and
+/// `'a'..='z'` are folded together); non-ASCII letters are never folded. Matches are still
+/// reported as offsets into the haystack exactly as given, and `find_as_strings` still returns
+/// the patterns exactly as given, not their case-folded forms.
+///
+/// `find`, `find_overlapping` and `find_overlapping_no_suffix` each have an `iter_*` counterpart
+/// (`iter_find`, etc.) that returns a lazy iterator instead of a list, for callers that want to
+/// stop early or avoid materializing every match up front.
+///
 /// :param patterns: List of string patterns.
 /// :param match_kind: A search option of the Aho-Corasick automaton.
+/// :param values: Optional list of non-negative integer values, one per pattern. Defaults to
+///     each pattern's index in `patterns`.
+/// :param case_insensitive: Fold ASCII letter case when matching.
 /// :type patterns: list[str]
 /// :type match_kind: int
+/// :type values: list[int] | None
+/// :type case_insensitive: bool
 /// :rtype: daachorse.Automaton
+/// :raises ValueError: if `values` is given and its length differs from `patterns`.
 #[pyclass]
 struct Automaton {
     pma: CharwiseDoubleArrayAhoCorasick<usize>,
     match_kind: MatchKind,
     patterns: Vec<Py<PyString>>,
+    // Public value for each pattern, indexed by the pattern's position (which is also the dense
+    // id the automaton itself reports via `m.value()`, since `pma` is always built over plain
+    // indices). `None` means the public value is just the index itself. Unlike a value -> index
+    // map, this stays correct when two patterns share a value, since it's a plain forward lookup.
+    values: Option<Vec<usize>>,
+    case_insensitive: bool,
+    // Longest pattern's length in chars, used to size the carry-over window in `find_stream`.
+    max_pattern_chars: usize,
 }
 
 #[pymethods]
 impl Automaton {
     #[new]
-    #[pyo3(signature = (patterns, /, match_kind = 0))]
-    fn new(py: Python, patterns: Vec<Py<PyString>>, match_kind: u8) -> PyResult<Self> {
+    #[pyo3(signature = (patterns, /, match_kind = 0, values = None, case_insensitive = false))]
+    fn new(
+        py: Python,
+        patterns: Vec<Py<PyString>>,
+        match_kind: u8,
+        values: Option<Vec<usize>>,
+        case_insensitive: bool,
+    ) -> PyResult<Self> {
         let raw_patterns: PyResult<Vec<String>> =
             patterns.iter().map(|pat| pat.extract(py)).collect();
         let raw_patterns = raw_patterns?;
+        let max_pattern_chars = raw_patterns
+            .iter()
+            .map(|pat| pat.chars().count())
+            .max()
+            .unwrap_or(0);
         let match_kind = MatchKind::from(match_kind);
+        let build_patterns: Vec<String> = if case_insensitive {
+            raw_patterns.iter().map(|pat| ascii_fold(pat)).collect()
+        } else {
+            raw_patterns
+        };
+        if let Some(values) = &values {
+            if values.len() != build_patterns.len() {
+                return Err(PyValueError::new_err(
+                    "values must have the same length as patterns",
+                ));
+            }
+        }
+        // Patterns are always built over their own dense index, regardless of `values`: the
+        // automaton only needs a unique id per pattern to report via `m.value()`, and `values` is
+        // then applied on top of that (see `value_of`) as a separate, possibly many-to-one,
+        // lookup. This keeps pattern resolution correct even when two patterns share a value.
+        let pma = py
+            .allow_threads(|| {
+                CharwiseDoubleArrayAhoCorasickBuilder::new()
+                    .match_kind(match_kind)
+                    .build(build_patterns)
+            })
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
         Ok(Self {
-            pma: py
-                .allow_threads(|| {
-                    CharwiseDoubleArrayAhoCorasickBuilder::new()
-                        .match_kind(match_kind)
-                        .build(raw_patterns)
-                })
-                .map_err(|e| PyValueError::new_err(e.to_string()))?,
+            pma,
             match_kind,
             patterns,
+            values,
+            case_insensitive,
+            max_pattern_chars,
         })
     }
 
@@ -75,41 +159,12 @@ impl Automaton {
     /// :rtype: list[tuple[int, int, int]]
     #[pyo3(text_signature = "($self, haystack, /)")]
     fn find(self_: PyRef<Self>, haystack: &str) -> PyResult<Vec<(usize, usize, usize)>> {
-        let mut pos_map = vec![0; haystack.len() + 1];
-        let mut len_in_chars = 0;
-        let match_kind = self_.match_kind;
         let py = self_.py();
-        let pma = &self_.pma;
-        Ok(py.allow_threads(|| unsafe {
-            for (i, (j, _)) in haystack.char_indices().enumerate() {
-                debug_assert!(j < pos_map.len());
-                *pos_map.get_unchecked_mut(j) = i;
-                len_in_chars = i;
-            }
-            *pos_map.last_mut().unwrap_unchecked() = len_in_chars + 1;
-            match match_kind {
-                MatchKind::Standard => pma
-                    .find_iter(haystack)
-                    .map(|m| {
-                        (
-                            *pos_map.get_unchecked(m.start()),
-                            *pos_map.get_unchecked(m.end()),
-                            m.value(),
-                        )
-                    })
-                    .collect(),
-                MatchKind::LeftmostLongest | MatchKind::LeftmostFirst => pma
-                    .leftmost_find_iter(haystack)
-                    .map(|m| {
-                        (
-                            *pos_map.get_unchecked(m.start()),
-                            *pos_map.get_unchecked(m.end()),
-                            m.value(),
-                        )
-                    })
-                    .collect(),
-            }
-        }))
+        Ok(self_
+            .non_overlapping_matches(py, haystack)
+            .into_iter()
+            .map(|(start, end, index)| (start, end, self_.value_of(index)))
+            .collect())
     }
 
     /// Returns a list of non-overlapping match strings in the given haystack.
@@ -140,19 +195,11 @@ impl Automaton {
     /// :rtype: list[str]
     #[pyo3(text_signature = "($self, haystack, /)")]
     fn find_as_strings(self_: PyRef<Self>, haystack: &str) -> PyResult<Vec<Py<PyString>>> {
-        let match_kind = self_.match_kind;
         let py = self_.py();
-        let pma = &self_.pma;
-        let pattern_ids: Vec<_> = py.allow_threads(|| match match_kind {
-            MatchKind::Standard => pma.find_iter(haystack).map(|m| m.value()).collect(),
-            MatchKind::LeftmostLongest | MatchKind::LeftmostFirst => pma
-                .leftmost_find_iter(haystack)
-                .map(|m| m.value())
-                .collect(),
-        });
-        Ok(pattern_ids
+        Ok(self_
+            .non_overlapping_matches(py, haystack)
             .into_iter()
-            .map(|i| unsafe { self_.patterns.get_unchecked(i).clone_ref(py) })
+            .map(|(_, _, index)| unsafe { self_.patterns.get_unchecked(index).clone_ref(py) })
             .collect())
     }
 
@@ -177,6 +224,8 @@ impl Automaton {
         if self_.match_kind != MatchKind::Standard {
             return Err(PyValueError::new_err("match_kind must be STANDARD"));
         }
+        let folded = self_.case_insensitive.then(|| ascii_fold(haystack));
+        let query = folded.as_deref().unwrap_or(haystack);
         let py = self_.py();
         let pma = &self_.pma;
         Ok(py.allow_threads(|| {
@@ -189,12 +238,12 @@ impl Automaton {
                     len_in_chars = i;
                 }
                 *pos_map.last_mut().unwrap_unchecked() = len_in_chars + 1;
-                pma.find_overlapping_iter(haystack)
+                pma.find_overlapping_iter(query)
                     .map(|m| {
                         (
                             *pos_map.get_unchecked(m.start()),
                             *pos_map.get_unchecked(m.end()),
-                            m.value(),
+                            self_.value_of(m.value()),
                         )
                     })
                     .collect()
@@ -223,10 +272,12 @@ impl Automaton {
         if self_.match_kind != MatchKind::Standard {
             return Err(PyValueError::new_err("match_kind must be STANDARD"));
         }
+        let folded = self_.case_insensitive.then(|| ascii_fold(haystack));
+        let query = folded.as_deref().unwrap_or(haystack);
         let py = self_.py();
         let pma = &self_.pma;
         let pattern_ids: Vec<_> = py.allow_threads(|| {
-            pma.find_overlapping_iter(haystack)
+            pma.find_overlapping_iter(query)
                 .map(|m| m.value())
                 .collect()
         });
@@ -263,6 +314,8 @@ impl Automaton {
         if self_.match_kind != MatchKind::Standard {
             return Err(PyValueError::new_err("match_kind must be STANDARD"));
         }
+        let folded = self_.case_insensitive.then(|| ascii_fold(haystack));
+        let query = folded.as_deref().unwrap_or(haystack);
         let py = self_.py();
         let pma = &self_.pma;
         Ok(py.allow_threads(|| {
@@ -275,12 +328,12 @@ impl Automaton {
                     len_in_chars = i;
                 }
                 *pos_map.last_mut().unwrap_unchecked() = len_in_chars + 1;
-                pma.find_overlapping_no_suffix_iter(haystack)
+                pma.find_overlapping_no_suffix_iter(query)
                     .map(|m| {
                         (
                             *pos_map.get_unchecked(m.start()),
                             *pos_map.get_unchecked(m.end()),
-                            m.value(),
+                            self_.value_of(m.value()),
                         )
                     })
                     .collect()
@@ -312,13 +365,776 @@ impl Automaton {
         self_: PyRef<Self>,
         haystack: &str,
     ) -> PyResult<Vec<Py<PyString>>> {
+        if self_.match_kind != MatchKind::Standard {
+            return Err(PyValueError::new_err("match_kind must be STANDARD"));
+        }
+        let folded = self_.case_insensitive.then(|| ascii_fold(haystack));
+        let query = folded.as_deref().unwrap_or(haystack);
+        let py = self_.py();
+        let pma = &self_.pma;
+        let pattern_ids: Vec<_> = py.allow_threads(|| {
+            pma.find_overlapping_no_suffix_iter(query)
+                .map(|m| m.value())
+                .collect()
+        });
+        Ok(pattern_ids
+            .into_iter()
+            .map(|i| unsafe { self_.patterns.get_unchecked(i).clone_ref(py) })
+            .collect())
+    }
+
+    /// Serializes the automaton into a self-contained binary blob.
+    ///
+    /// The blob embeds the original patterns and `values` (stored one per pattern, in pattern
+    /// order, so patterns that share a value round-trip correctly) alongside the automaton's
+    /// double-array representation, so it can be rebuilt with :meth:`deserialize` without
+    /// rebuilding the automaton from scratch.
+    ///
+    /// Examples:
+    ///     >>> import daachorse
+    ///     >>> patterns = ['bcd', 'ab', 'a']
+    ///     >>> pma = daachorse.Automaton(patterns)
+    ///     >>> data = pma.serialize()
+    ///     >>> pma2 = daachorse.Automaton.deserialize(data)
+    ///     >>> pma2.find('abcd')
+    ///     [(0, 1, 2), (1, 4, 0)]
+    ///
+    /// :rtype: bytes
+    #[pyo3(text_signature = "($self, /)")]
+    fn serialize(self_: PyRef<Self>) -> PyResult<Py<PyBytes>> {
+        let py = self_.py();
+        let mut buf = vec![SERIALIZE_FORMAT_VERSION, self_.match_kind as u8];
+        buf.extend_from_slice(&(self_.patterns.len() as u64).to_le_bytes());
+        for pattern in &self_.patterns {
+            let pattern: &str = pattern.extract(py)?;
+            let bytes = pattern.as_bytes();
+            buf.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            buf.extend_from_slice(bytes);
+        }
+        // One entry per pattern, in pattern order, mirroring `values` itself: this stays exact
+        // even when several patterns share a value, unlike reconstructing it from a value -> index
+        // map.
+        match &self_.values {
+            Some(values) => {
+                buf.push(1);
+                for &value in values {
+                    buf.extend_from_slice(&(value as u64).to_le_bytes());
+                }
+            }
+            None => buf.push(0),
+        }
+        buf.push(self_.case_insensitive as u8);
+        buf.extend_from_slice(&self_.pma.serialize());
+        Ok(PyBytes::new_bound(py, &buf).into())
+    }
+
+    /// Rebuilds an automaton previously produced by :meth:`serialize`.
+    ///
+    /// :param data: Binary blob produced by :meth:`serialize`.
+    /// :type data: bytes
+    /// :rtype: daachorse.Automaton
+    /// :raises ValueError: if `data` was not produced by this version of :meth:`serialize`.
+    #[classmethod]
+    #[pyo3(text_signature = "(data, /)")]
+    fn deserialize(_cls: &Bound<PyType>, py: Python, data: &[u8]) -> PyResult<Self> {
+        if data.len() < 1 + 1 + 8 {
+            return Err(PyValueError::new_err("truncated serialized automaton"));
+        }
+        let version = data[0];
+        if version != SERIALIZE_FORMAT_VERSION {
+            return Err(PyValueError::new_err(format!(
+                "unsupported serialization format version: {version}"
+            )));
+        }
+        let match_kind = MatchKind::from(data[1]);
+        let mut offset = 2;
+        let num_patterns =
+            u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as usize;
+        offset += 8;
+        // Each pattern contributes at least its own 8-byte length prefix, so this bounds
+        // `num_patterns` against the bytes actually available before trusting it to size an
+        // allocation; a garbage length field then degrades to a clean truncation error below
+        // instead of a capacity-driven allocation abort.
+        if num_patterns > (data.len() - offset) / 8 {
+            return Err(PyValueError::new_err("truncated serialized automaton"));
+        }
+        let mut patterns = Vec::with_capacity(num_patterns);
+        let mut max_pattern_chars = 0;
+        for _ in 0..num_patterns {
+            if data.len() < offset + 8 {
+                return Err(PyValueError::new_err("truncated serialized automaton"));
+            }
+            let len = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as usize;
+            offset += 8;
+            if data.len() < offset + len {
+                return Err(PyValueError::new_err("truncated serialized automaton"));
+            }
+            let pattern = std::str::from_utf8(&data[offset..offset + len])
+                .map_err(|e| PyValueError::new_err(e.to_string()))?;
+            max_pattern_chars = max_pattern_chars.max(pattern.chars().count());
+            patterns.push(PyString::new_bound(py, pattern).unbind());
+            offset += len;
+        }
+        if data.len() < offset + 1 {
+            return Err(PyValueError::new_err("truncated serialized automaton"));
+        }
+        let has_values = data[offset];
+        offset += 1;
+        let values = if has_values == 1 {
+            let mut values = Vec::with_capacity(num_patterns);
+            for _ in 0..num_patterns {
+                if data.len() < offset + 8 {
+                    return Err(PyValueError::new_err("truncated serialized automaton"));
+                }
+                let value = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+                offset += 8;
+                values.push(value as usize);
+            }
+            Some(values)
+        } else {
+            None
+        };
+        if data.len() < offset + 1 {
+            return Err(PyValueError::new_err("truncated serialized automaton"));
+        }
+        let case_insensitive = data[offset] == 1;
+        offset += 1;
+        let (pma, rest) = unsafe {
+            CharwiseDoubleArrayAhoCorasick::<usize>::deserialize_unchecked(&data[offset..])
+        };
+        if !rest.is_empty() {
+            return Err(PyValueError::new_err(
+                "trailing bytes after the serialized automaton",
+            ));
+        }
+        Ok(Self {
+            pma,
+            match_kind,
+            patterns,
+            values,
+            case_insensitive,
+            max_pattern_chars,
+        })
+    }
+
+    /// Searches an iterable of text chunks as though it were one continuous haystack.
+    ///
+    /// This is for scanning files or network streams that don't fit in memory: each chunk is
+    /// searched together with a carried-over suffix of the stream so far (at most one char
+    /// shorter than the longest pattern, which is enough for any match that reaches into the new
+    /// chunk), so matches spanning a chunk boundary are still found, without ever
+    /// holding the whole stream in memory at once. Offsets in the returned tuples are char
+    /// offsets into the concatenation of all chunks, exactly as if `find`/`find_overlapping` had
+    /// been called on that concatenation.
+    ///
+    /// Standard semantics are exact across chunk boundaries. Leftmost semantics are approximate
+    /// near a boundary, since a leftmost match decision may need to see further ahead than the
+    /// carried-over suffix provides; for exact leftmost results, flush the whole stream into one
+    /// chunk before the final call.
+    ///
+    /// (Standard's exactness relies on never carrying already-consumed characters into the next
+    /// window: the next carry starts no earlier than the end of the last accepted match, so each
+    /// window's non-overlapping scan resumes exactly where the true stream scan would, instead of
+    /// restarting from inside a match and producing a different segmentation.)
+    ///
+    /// :param chunks: Iterable of string chunks, in stream order.
+    /// :type chunks: collections.abc.Iterable[str]
+    /// :rtype: list[tuple[int, int, int]]
+    #[pyo3(text_signature = "($self, chunks, /)")]
+    fn find_stream(
+        self_: PyRef<Self>,
+        chunks: &Bound<PyAny>,
+    ) -> PyResult<Vec<(usize, usize, usize)>> {
+        let match_kind = self_.match_kind;
+        let case_insensitive = self_.case_insensitive;
+        let overlap_chars = self_.max_pattern_chars.saturating_sub(1);
+        let py = self_.py();
+        let pma = &self_.pma;
+
+        let mut results = Vec::new();
+        let mut carry = String::new();
+        // Char offset, in the overall stream, at which `carry` (the start of the next window)
+        // begins.
+        let mut carry_start = 0usize;
+        // Global end offset of the last Standard match accepted into `results`. Each window
+        // re-scans the whole carried suffix, so a non-overlapping Standard match already emitted
+        // last iteration would otherwise be rediscovered (with a different, now stale, successor
+        // match) every time it reappears in a later window; any match starting before this offset
+        // is exactly that rediscovery, not a new match, and is dropped. Unused by Leftmost, which
+        // instead relies on the `end > carry_chars` check below.
+        let mut consumed_until = 0usize;
+
+        for chunk in chunks.iter()? {
+            let chunk: String = chunk?.extract()?;
+            let carry_chars = carry.chars().count();
+            let mut window = carry;
+            window.push_str(&chunk);
+
+            let folded;
+            let query: &str = if case_insensitive {
+                folded = ascii_fold(&window);
+                &folded
+            } else {
+                &window
+            };
+
+            let mut pos_map = vec![0usize; query.len() + 1];
+            let mut len_in_chars = 0;
+            py.allow_threads(|| unsafe {
+                for (i, (j, _)) in query.char_indices().enumerate() {
+                    *pos_map.get_unchecked_mut(j) = i;
+                    len_in_chars = i;
+                }
+                *pos_map.last_mut().unwrap_unchecked() = len_in_chars + 1;
+                let window_matches: Vec<(usize, usize, usize)> = match match_kind {
+                    MatchKind::Standard => pma
+                        .find_iter(query)
+                        .map(|m| {
+                            (
+                                *pos_map.get_unchecked(m.start()),
+                                *pos_map.get_unchecked(m.end()),
+                                m.value(),
+                            )
+                        })
+                        .collect(),
+                    MatchKind::LeftmostLongest | MatchKind::LeftmostFirst => pma
+                        .leftmost_find_iter(query)
+                        .map(|m| {
+                            (
+                                *pos_map.get_unchecked(m.start()),
+                                *pos_map.get_unchecked(m.end()),
+                                m.value(),
+                            )
+                        })
+                        .collect(),
+                };
+                match match_kind {
+                    MatchKind::Standard => {
+                        // Non-overlapping matches are position-dependent: re-scanning the carried
+                        // suffix from scratch can redeclare a match `find` already consumed further
+                        // back, so the cut is by global start offset rather than by `carry_chars`.
+                        for (start, end, index) in window_matches {
+                            let global_start = carry_start + start;
+                            let global_end = carry_start + end;
+                            if global_start >= consumed_until {
+                                results.push((global_start, global_end, index));
+                                consumed_until = global_end;
+                            }
+                        }
+                    }
+                    MatchKind::LeftmostLongest | MatchKind::LeftmostFirst => {
+                        // A match entirely inside the carried prefix was already discovered (with
+                        // full right-context) on the previous iteration; only matches that reach
+                        // into the freshly appended chunk are new.
+                        for (start, end, index) in window_matches {
+                            if end > carry_chars {
+                                results.push((carry_start + start, carry_start + end, index));
+                            }
+                        }
+                    }
+                }
+            });
+
+            // The next window's carry must start no earlier than `floor`: for Standard that's
+            // `consumed_until`, since any earlier position was already consumed by an accepted
+            // match and re-including it would rescan from inside that match rather than resuming
+            // after it, desyncing the window from the true stream segmentation (e.g. two
+            // back-to-back "aa" matches would otherwise collapse into one). Leftmost has no such
+            // accepted-match tracking, so its floor is just the previous carry start, preserving
+            // the plain fixed-size trailing window it always used.
+            let stream_end = carry_start + window.chars().count();
+            let floor = match match_kind {
+                MatchKind::Standard => consumed_until,
+                MatchKind::LeftmostLongest | MatchKind::LeftmostFirst => carry_start,
+            };
+            let next_carry_start = floor.max(stream_end.saturating_sub(overlap_chars));
+            carry = window
+                .chars()
+                .skip(next_carry_start - carry_start)
+                .collect();
+            carry_start = next_carry_start;
+        }
+        Ok(results
+            .into_iter()
+            .map(|(start, end, index)| (start, end, self_.value_of(index)))
+            .collect())
+    }
+
+    /// Returns a copy of `haystack` with every non-overlapping match substituted.
+    ///
+    /// `replacements` is indexed by pattern value (the third element `find` reports): for the
+    /// default automaton this is simply the pattern's index, so `replacements[i]` replaces
+    /// occurrences of `patterns[i]`.
+    ///
+    /// Examples:
+    ///     >>> import daachorse
+    ///     >>> patterns = ['bcd', 'ab', 'a']
+    ///     >>> pma = daachorse.Automaton(patterns)
+    ///     >>> pma.replace_all('abcd', ['X', 'Y', 'Z'])
+    ///     'ZX'
+    ///
+    /// :param haystack: String to search for.
+    /// :param replacements: Replacement strings, indexed by pattern value.
+    /// :type haystack: str
+    /// :type replacements: list[str]
+    /// :rtype: str
+    /// :raises ValueError: if a match's value has no corresponding entry in `replacements`.
+    #[pyo3(text_signature = "($self, haystack, replacements, /)")]
+    fn replace_all(
+        self_: PyRef<Self>,
+        haystack: &str,
+        replacements: Vec<Py<PyString>>,
+    ) -> PyResult<Py<PyString>> {
+        let py = self_.py();
+        let byte_offsets = char_byte_offsets(haystack);
+        let mut out = String::with_capacity(haystack.len());
+        let mut last_char = 0;
+        for (start, end, index) in self_.non_overlapping_matches(py, haystack) {
+            out.push_str(&haystack[byte_offsets[last_char]..byte_offsets[start]]);
+            let replacement: &str = replacements
+                .get(self_.value_of(index))
+                .ok_or_else(|| {
+                    PyValueError::new_err("replacements has no entry for a matched pattern value")
+                })?
+                .extract(py)?;
+            out.push_str(replacement);
+            last_char = end;
+        }
+        out.push_str(&haystack[byte_offsets[last_char]..]);
+        Ok(PyString::new_bound(py, &out).unbind())
+    }
+
+    /// Returns a copy of `haystack` with every non-overlapping match replaced by the result of
+    /// calling `callback(start, end, value, matched_text)`.
+    ///
+    /// Unlike `replace_all`, this does not require pre-computing a replacement per pattern,
+    /// which makes it suited to dynamic rewriting such as redaction or templating.
+    ///
+    /// Examples:
+    ///     >>> import daachorse
+    ///     >>> patterns = ['bcd', 'ab', 'a']
+    ///     >>> pma = daachorse.Automaton(patterns)
+    ///     >>> pma.replace_all_with('abcd', lambda start, end, value, text: text.upper())
+    ///     'ABCD'
+    ///
+    /// :param haystack: String to search for.
+    /// :param callback: Called as `callback(start, end, value, matched_text)` for each match;
+    ///     its return value is spliced in as the replacement.
+    /// :type haystack: str
+    /// :type callback: collections.abc.Callable[[int, int, int, str], str]
+    /// :rtype: str
+    #[pyo3(text_signature = "($self, haystack, callback, /)")]
+    fn replace_all_with(
+        self_: PyRef<Self>,
+        haystack: &str,
+        callback: &Bound<PyAny>,
+    ) -> PyResult<Py<PyString>> {
+        let py = self_.py();
+        let byte_offsets = char_byte_offsets(haystack);
+        let mut out = String::with_capacity(haystack.len());
+        let mut last_char = 0;
+        for (start, end, index) in self_.non_overlapping_matches(py, haystack) {
+            out.push_str(&haystack[byte_offsets[last_char]..byte_offsets[start]]);
+            let matched_text = &haystack[byte_offsets[start]..byte_offsets[end]];
+            let replacement: String = callback
+                .call1((start, end, self_.value_of(index), matched_text))?
+                .extract()?;
+            out.push_str(&replacement);
+            last_char = end;
+        }
+        out.push_str(&haystack[byte_offsets[last_char]..]);
+        Ok(PyString::new_bound(py, &out).unbind())
+    }
+
+    /// Like `find`, but returns an iterator instead of a list.
+    ///
+    /// This avoids materializing every match up front, so a caller that only needs the first few
+    /// matches (or wants to `break` early) doesn't pay for the rest.
+    ///
+    /// :param haystack: String to search for.
+    /// :type haystack: str
+    /// :rtype: Iterator[tuple[int, int, int]]
+    #[pyo3(text_signature = "($self, haystack, /)")]
+    fn iter_find(self_: PyRef<Self>, haystack: String) -> PyResult<Py<FindIter>> {
+        let py = self_.py();
+        let match_kind = self_.match_kind;
+        let case_insensitive = self_.case_insensitive;
+        let automaton: Py<Automaton> = self_.into();
+        let iter =
+            FindIter::new(
+                py,
+                automaton,
+                haystack,
+                case_insensitive,
+                |pma, query| match match_kind {
+                    MatchKind::Standard => Box::new(
+                        pma.find_iter(query)
+                            .map(|m| (m.start(), m.end(), m.value())),
+                    ),
+                    MatchKind::LeftmostLongest | MatchKind::LeftmostFirst => Box::new(
+                        pma.leftmost_find_iter(query)
+                            .map(|m| (m.start(), m.end(), m.value())),
+                    ),
+                },
+            );
+        Py::new(py, iter)
+    }
+
+    /// Like `find_overlapping`, but returns an iterator instead of a list.
+    ///
+    /// :param haystack: String to search for.
+    /// :type haystack: str
+    /// :rtype: Iterator[tuple[int, int, int]]
+    /// :raises ValueError: if the automaton is built with a LESTMOST option.
+    #[pyo3(text_signature = "($self, haystack, /)")]
+    fn iter_find_overlapping(self_: PyRef<Self>, haystack: String) -> PyResult<Py<FindIter>> {
+        if self_.match_kind != MatchKind::Standard {
+            return Err(PyValueError::new_err("match_kind must be STANDARD"));
+        }
+        let py = self_.py();
+        let case_insensitive = self_.case_insensitive;
+        let automaton: Py<Automaton> = self_.into();
+        let iter = FindIter::new(py, automaton, haystack, case_insensitive, |pma, query| {
+            Box::new(
+                pma.find_overlapping_iter(query)
+                    .map(|m| (m.start(), m.end(), m.value())),
+            )
+        });
+        Py::new(py, iter)
+    }
+
+    /// Like `find_overlapping_no_suffix`, but returns an iterator instead of a list.
+    ///
+    /// :param haystack: String to search for.
+    /// :type haystack: str
+    /// :rtype: Iterator[tuple[int, int, int]]
+    /// :raises ValueError: if the automaton is built with a LESTMOST option.
+    #[pyo3(text_signature = "($self, haystack, /)")]
+    fn iter_find_overlapping_no_suffix(
+        self_: PyRef<Self>,
+        haystack: String,
+    ) -> PyResult<Py<FindIter>> {
+        if self_.match_kind != MatchKind::Standard {
+            return Err(PyValueError::new_err("match_kind must be STANDARD"));
+        }
+        let py = self_.py();
+        let case_insensitive = self_.case_insensitive;
+        let automaton: Py<Automaton> = self_.into();
+        let iter = FindIter::new(py, automaton, haystack, case_insensitive, |pma, query| {
+            Box::new(
+                pma.find_overlapping_no_suffix_iter(query)
+                    .map(|m| (m.start(), m.end(), m.value())),
+            )
+        });
+        Py::new(py, iter)
+    }
+}
+
+impl Automaton {
+    /// Resolves a pattern's dense index (what the automaton itself reports via `m.value()`, and
+    /// also `patterns`' index for that pattern) to its public value, i.e. what `values` mapped it
+    /// to at construction, or the index itself if `values` was not given. This is a plain
+    /// index -> value lookup, so unlike a value -> index map it stays correct even when several
+    /// patterns share a value.
+    fn value_of(&self, index: usize) -> usize {
+        self.values.as_ref().map_or(index, |values| values[index])
+    }
+
+    /// Returns non-overlapping matches in `haystack` as `(start, end, pattern_index)`, dispatching
+    /// on `match_kind` and folding ASCII case if `case_insensitive` is set. Shared by `find`,
+    /// `find_as_strings`, `replace_all` and `replace_all_with`, which all need the same
+    /// leftmost-vs-standard match set and only differ in what they do with it; callers that
+    /// report a public value rather than resolve pattern text must map the index through
+    /// `value_of` themselves.
+    fn non_overlapping_matches(&self, py: Python, haystack: &str) -> Vec<(usize, usize, usize)> {
+        let folded = self.case_insensitive.then(|| ascii_fold(haystack));
+        let query = folded.as_deref().unwrap_or(haystack);
+        let mut pos_map = vec![0; haystack.len() + 1];
+        let mut len_in_chars = 0;
+        let match_kind = self.match_kind;
+        let pma = &self.pma;
+        py.allow_threads(|| unsafe {
+            for (i, (j, _)) in haystack.char_indices().enumerate() {
+                debug_assert!(j < pos_map.len());
+                *pos_map.get_unchecked_mut(j) = i;
+                len_in_chars = i;
+            }
+            *pos_map.last_mut().unwrap_unchecked() = len_in_chars + 1;
+            match match_kind {
+                MatchKind::Standard => pma
+                    .find_iter(query)
+                    .map(|m| {
+                        (
+                            *pos_map.get_unchecked(m.start()),
+                            *pos_map.get_unchecked(m.end()),
+                            m.value(),
+                        )
+                    })
+                    .collect(),
+                MatchKind::LeftmostLongest | MatchKind::LeftmostFirst => pma
+                    .leftmost_find_iter(query)
+                    .map(|m| {
+                        (
+                            *pos_map.get_unchecked(m.start()),
+                            *pos_map.get_unchecked(m.end()),
+                            m.value(),
+                        )
+                    })
+                    .collect(),
+            }
+        })
+    }
+}
+
+/// Iterator returned by `Automaton.iter_find` and its `_overlapping` variants.
+///
+/// Pulls one match at a time out of daachorse instead of collecting a full list up front, so a
+/// caller that only needs the first few matches (or wants to `break` early) doesn't pay for the
+/// rest. Internally this holds the (possibly case-folded) haystack and a handle to the owning
+/// `Automaton` alive for as long as the iterator is, since the match iterator it wraps borrows
+/// from both.
+#[pyclass]
+struct FindIter {
+    // Kept alive only so the borrow `inner` holds into its `pma` stays valid. `Automaton` has no
+    // method that mutates `pma` after construction, so sharing an immutable borrow of it for as
+    // long as this field is alive is sound.
+    _automaton: Py<Automaton>,
+    // Same reasoning: `inner` borrows from this buffer. It is never mutated after construction,
+    // so its backing allocation stays put even though this `String` handle may itself move.
+    _haystack: String,
+    inner: Box<dyn Iterator<Item = (usize, usize, usize)> + Send>,
+}
+
+#[pymethods]
+impl FindIter {
+    fn __iter__(self_: PyRef<Self>) -> PyRef<Self> {
+        self_
+    }
+
+    fn __next__(mut self_: PyRefMut<Self>) -> PyResult<Option<(usize, usize, usize)>> {
+        // `inner` holds a `'static`-asserted borrow into `_automaton.pma` (see the safety note on
+        // `new` below). That's only sound for as long as nothing holds a mutable borrow of
+        // `_automaton` at the same time; `Automaton` has no method that takes `PyRefMut`, but
+        // nothing stops one from being added later and silently invalidating this. Re-check the
+        // invariant on every call instead of trusting that absence to hold forever.
+        self_._automaton.try_borrow(self_.py()).map_err(|_| {
+            PyValueError::new_err(
+                "automaton is mutably borrowed elsewhere; this FindIter is unsound to continue",
+            )
+        })?;
+        Ok(self_.inner.next())
+    }
+}
+
+impl FindIter {
+    /// Builds a `FindIter` over `haystack`, case-folding it first if `case_insensitive` is set.
+    /// `build` turns the automaton and the resulting query string into a byte-offset match
+    /// iterator; this wraps it to translate byte offsets to char offsets via a `pos_map`
+    /// computed once up front, same as the eager `find`-family methods.
+    ///
+    /// Safety: the iterator `build` returns borrows from its two arguments, so `new` ties its
+    /// lifetime to a fresh generic `'p` and then unsafely asserts `pma` and `query` have that
+    /// lifetime. This is sound because `automaton` and `query_owned`, which `pma` and `query`
+    /// respectively point into, are stored in the returned `FindIter` right below and are never
+    /// mutated (so their backing allocations never move or get freed) for as long as it lives.
+    /// `pma`'s transmute in particular is only taken through a `PyRef` borrowed and dropped right
+    /// here — there is no runtime borrow held for the `FindIter`'s lifetime, only the invariant
+    /// that nothing else ever takes `PyRefMut<Automaton>`; `__next__` re-asserts that invariant
+    /// with `try_borrow` on every call instead of relying on it silently continuing to hold.
+    fn new<'p, F>(
+        py: Python,
+        automaton: Py<Automaton>,
+        haystack: String,
+        case_insensitive: bool,
+        build: F,
+    ) -> Self
+    where
+        F: FnOnce(
+            &'p CharwiseDoubleArrayAhoCorasick<usize>,
+            &'p str,
+        ) -> Box<dyn Iterator<Item = (usize, usize, usize)> + 'p>,
+    {
+        let query_owned = if case_insensitive {
+            ascii_fold(&haystack)
+        } else {
+            haystack
+        };
+
+        let mut pos_map = vec![0usize; query_owned.len() + 1];
+        let mut len_in_chars = 0;
+        unsafe {
+            for (i, (j, _)) in query_owned.char_indices().enumerate() {
+                *pos_map.get_unchecked_mut(j) = i;
+                len_in_chars = i;
+            }
+            *pos_map.last_mut().unwrap_unchecked() = len_in_chars + 1;
+        }
+
+        // Safety: see the function doc comment above.
+        let query: &'p str = unsafe { std::mem::transmute::<&str, &'p str>(query_owned.as_str()) };
+        let borrowed = automaton.borrow(py);
+        let values = borrowed.values.clone();
+        let pma: &'p CharwiseDoubleArrayAhoCorasick<usize> =
+            unsafe { std::mem::transmute(&borrowed.pma) };
+
+        let inner = build(pma, query).map(move |(start, end, index)| unsafe {
+            (
+                *pos_map.get_unchecked(start),
+                *pos_map.get_unchecked(end),
+                values.as_ref().map_or(index, |v| v[index]),
+            )
+        });
+
+        Self {
+            _automaton: automaton,
+            _haystack: query_owned,
+            inner: Box::new(inner),
+        }
+    }
+}
+
+/// Returns the byte offset of each char boundary in `haystack`, plus a trailing entry for
+/// `haystack.len()`, so that `offsets[c]..offsets[c + 1]` is the byte span of char index `c`.
+fn char_byte_offsets(haystack: &str) -> Vec<usize> {
+    let mut offsets: Vec<usize> = haystack.char_indices().map(|(i, _)| i).collect();
+    offsets.push(haystack.len());
+    offsets
+}
+
+/// An Aho-Corasick automaton over raw bytes, using the compact double-array data structure.
+///
+/// Unlike `Automaton`, this searches `bytes` haystacks and `bytes` patterns directly, with no
+/// UTF-8 char-index remapping, which makes it the faster choice for binary data or text that is
+/// already known to be single-byte-per-char (e.g. Latin-1 logs).
+///
+/// Examples:
+///     >>> import daachorse
+///     >>> patterns = [b'bcd', b'ab', b'a']
+///     >>> pma = daachorse.BytesAutomaton(patterns)
+///     >>> pma.find(b'abcd')
+///     [(0, 1, 2), (1, 4, 0)]
+///
+/// :param patterns: List of bytes patterns.
+/// :param match_kind: A search option of the Aho-Corasick automaton.
+/// :type patterns: list[bytes]
+/// :type match_kind: int
+/// :rtype: daachorse.BytesAutomaton
+#[pyclass]
+struct BytesAutomaton {
+    pma: DoubleArrayAhoCorasick<usize>,
+    match_kind: MatchKind,
+    patterns: Vec<Py<PyBytes>>,
+}
+
+#[pymethods]
+impl BytesAutomaton {
+    #[new]
+    #[pyo3(signature = (patterns, /, match_kind = 0))]
+    fn new(py: Python, patterns: Vec<Py<PyBytes>>, match_kind: u8) -> PyResult<Self> {
+        let raw_patterns: Vec<Vec<u8>> = patterns
+            .iter()
+            .map(|pat| pat.bind(py).as_bytes().to_vec())
+            .collect();
+        let match_kind = MatchKind::from(match_kind);
+        Ok(Self {
+            pma: py
+                .allow_threads(|| {
+                    DoubleArrayAhoCorasickBuilder::new()
+                        .match_kind(match_kind)
+                        .build(raw_patterns)
+                })
+                .map_err(|e| PyValueError::new_err(e.to_string()))?,
+            match_kind,
+            patterns,
+        })
+    }
+
+    /// Returns a list of non-overlapping matches in the given haystack.
+    ///
+    /// :param haystack: Bytes to search for.
+    /// :type haystack: bytes
+    /// :rtype: list[tuple[int, int, int]]
+    #[pyo3(text_signature = "($self, haystack, /)")]
+    fn find(self_: PyRef<Self>, haystack: &[u8]) -> PyResult<Vec<(usize, usize, usize)>> {
+        let match_kind = self_.match_kind;
+        let py = self_.py();
+        let pma = &self_.pma;
+        Ok(py.allow_threads(|| match match_kind {
+            MatchKind::Standard => pma
+                .find_iter(haystack)
+                .map(|m| (m.start(), m.end(), m.value()))
+                .collect(),
+            MatchKind::LeftmostLongest | MatchKind::LeftmostFirst => pma
+                .leftmost_find_iter(haystack)
+                .map(|m| (m.start(), m.end(), m.value()))
+                .collect(),
+        }))
+    }
+
+    /// Returns a list of non-overlapping match bytes in the given haystack.
+    ///
+    /// :param haystack: Bytes to search for.
+    /// :type haystack: bytes
+    /// :rtype: list[bytes]
+    #[pyo3(text_signature = "($self, haystack, /)")]
+    fn find_as_strings(self_: PyRef<Self>, haystack: &[u8]) -> PyResult<Vec<Py<PyBytes>>> {
+        let match_kind = self_.match_kind;
+        let py = self_.py();
+        let pma = &self_.pma;
+        let pattern_ids: Vec<_> = py.allow_threads(|| match match_kind {
+            MatchKind::Standard => pma.find_iter(haystack).map(|m| m.value()).collect(),
+            MatchKind::LeftmostLongest | MatchKind::LeftmostFirst => pma
+                .leftmost_find_iter(haystack)
+                .map(|m| m.value())
+                .collect(),
+        });
+        Ok(pattern_ids
+            .into_iter()
+            .map(|i| unsafe { self_.patterns.get_unchecked(i).clone_ref(py) })
+            .collect())
+    }
+
+    /// Returns a list of overlapping matches in the given haystack.
+    ///
+    /// :param haystack: Bytes to search for.
+    /// :type haystack: bytes
+    /// :rtype: list[tuple[int, int, int]]
+    /// :raises ValueError: if the automaton is built with a LESTMOST option.
+    #[pyo3(text_signature = "($self, haystack, /)")]
+    fn find_overlapping(
+        self_: PyRef<Self>,
+        haystack: &[u8],
+    ) -> PyResult<Vec<(usize, usize, usize)>> {
+        if self_.match_kind != MatchKind::Standard {
+            return Err(PyValueError::new_err("match_kind must be STANDARD"));
+        }
+        let py = self_.py();
+        let pma = &self_.pma;
+        Ok(py.allow_threads(|| {
+            pma.find_overlapping_iter(haystack)
+                .map(|m| (m.start(), m.end(), m.value()))
+                .collect()
+        }))
+    }
+
+    /// Returns a list of overlapping match bytes in the given haystack.
+    ///
+    /// :param haystack: Bytes to search for.
+    /// :type haystack: bytes
+    /// :rtype: list[bytes]
+    /// :raises ValueError: if the automaton is built with a LESTMOST option.
+    #[pyo3(text_signature = "($self, haystack, /)")]
+    fn find_overlapping_as_strings(
+        self_: PyRef<Self>,
+        haystack: &[u8],
+    ) -> PyResult<Vec<Py<PyBytes>>> {
         if self_.match_kind != MatchKind::Standard {
             return Err(PyValueError::new_err("match_kind must be STANDARD"));
         }
         let py = self_.py();
         let pma = &self_.pma;
         let pattern_ids: Vec<_> = py.allow_threads(|| {
-            pma.find_overlapping_no_suffix_iter(haystack)
+            pma.find_overlapping_iter(haystack)
                 .map(|m| m.value())
                 .collect()
         });
@@ -332,6 +1148,8 @@ impl Automaton {
 #[pymodule]
 fn daachorse(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Automaton>()?;
+    m.add_class::<BytesAutomaton>()?;
+    m.add_class::<FindIter>()?;
     m.add("MATCH_KIND_STANDARD", MatchKind::Standard as u8)?;
     m.add(
         "MATCH_KIND_LEFTMOST_LONGEST",